@@ -0,0 +1,68 @@
+use acme::{crypto::account_key::AccountKey, AcmeError};
+use hmac::{Hmac, Mac};
+use serde::Serialize;
+use sha2::Sha256;
+
+use crate::error::{LocalcertError, LocalcertResult};
+
+#[derive(Clone, Debug)]
+pub struct ExternalAccountBindingKey {
+    pub key_id: String,
+    pub hmac_key: Vec<u8>,
+}
+
+impl ExternalAccountBindingKey {
+    pub fn new(key_id: impl Into<String>, hmac_key_base64url: impl AsRef<str>) -> LocalcertResult<Self> {
+        let hmac_key = base64::decode_config(hmac_key_base64url.as_ref(), base64::URL_SAFE_NO_PAD)
+            .map_err(|err| LocalcertError::StateError(format!("invalid EAB HMAC key: {}", err)))?;
+        Ok(Self {
+            key_id: key_id.into(),
+            hmac_key,
+        })
+    }
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct EabProtectedHeader<'a> {
+    alg: &'static str,
+    kid: &'a str,
+    url: &'a str,
+}
+
+#[derive(Serialize)]
+pub struct ExternalAccountBinding {
+    pub protected: String,
+    pub payload: String,
+    pub signature: String,
+}
+
+pub(crate) fn build_eab_jws(
+    eab_key: &ExternalAccountBindingKey,
+    account_key: &AccountKey,
+    new_account_url: &str,
+) -> LocalcertResult<ExternalAccountBinding> {
+    let protected_header = EabProtectedHeader {
+        alg: "HS256",
+        kid: &eab_key.key_id,
+        url: new_account_url,
+    };
+    let protected = base64::encode_config(
+        serde_json::to_vec(&protected_header)?,
+        base64::URL_SAFE_NO_PAD,
+    );
+
+    let account_jwk = account_key.public_jwk().map_err(AcmeError::CryptoError)?;
+    let payload = base64::encode_config(account_jwk.as_bytes(), base64::URL_SAFE_NO_PAD);
+
+    let mut mac = <Hmac<Sha256> as Mac>::new_from_slice(&eab_key.hmac_key)
+        .map_err(|_| LocalcertError::StateError("invalid EAB HMAC key length".to_string()))?;
+    mac.update(format!("{}.{}", protected, payload).as_bytes());
+    let signature = base64::encode_config(mac.finalize().into_bytes(), base64::URL_SAFE_NO_PAD);
+
+    Ok(ExternalAccountBinding {
+        protected,
+        payload,
+        signature,
+    })
+}
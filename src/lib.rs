@@ -1,25 +1,42 @@
+pub mod eab;
 pub mod error;
+#[cfg(feature = "rustls")]
+pub mod manager;
+pub mod retry;
 pub mod states;
 pub mod wire;
 
 use std::{sync::Arc, time::Duration};
 
 use acme::{api::account::Account, Client};
+use eab::ExternalAccountBindingKey;
 use error::LocalcertResult;
 use http_client::HttpClient;
+use retry::RetryPolicy;
+use serde::{Deserialize, Serialize};
 use states::RegisteredState;
 use wire::client::LocalcertClient;
 
 pub use acme::api::client::RegisterAccountConfig;
 pub use acme::crypto::account_key::AccountKey;
 
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct AccountCredentials {
+    pub account_url: String,
+    pub account_key_jwk: String,
+    pub directory_url: String,
+}
+
 pub static DEFAULT_SERVER_URL: &str = "https://localcert.dev";
 pub static DEFAULT_ACME_POLLING_INTERVAL: Duration = Duration::from_secs(5);
+pub static DEFAULT_ACME_POLLING_TIMEOUT: Duration = Duration::from_secs(10 * 60);
 
 pub struct ConfigBuilder {
     http_client: Arc<dyn HttpClient>,
     server_url: Option<String>,
     acme_polling_interval: Duration,
+    acme_polling_timeout: Duration,
+    retry_policy: RetryPolicy,
 }
 
 impl ConfigBuilder {
@@ -33,6 +50,8 @@ impl ConfigBuilder {
             http_client: Arc::new(http_client),
             server_url: None,
             acme_polling_interval: DEFAULT_ACME_POLLING_INTERVAL,
+            acme_polling_timeout: DEFAULT_ACME_POLLING_TIMEOUT,
+            retry_policy: RetryPolicy::default(),
         }
     }
 
@@ -46,6 +65,16 @@ impl ConfigBuilder {
         self
     }
 
+    pub fn acme_polling_timeout(&mut self, timeout: impl Into<Duration>) -> &mut Self {
+        self.acme_polling_timeout = timeout.into();
+        self
+    }
+
+    pub fn retry_policy(&mut self, retry_policy: RetryPolicy) -> &mut Self {
+        self.retry_policy = retry_policy;
+        self
+    }
+
     pub async fn register_new_account(
         self,
         acme_directory_url: impl AsRef<str>,
@@ -58,6 +87,30 @@ impl ConfigBuilder {
         self.build_with_account(account)
     }
 
+    pub async fn register_new_account_with_eab(
+        self,
+        acme_directory_url: impl AsRef<str>,
+        register_config: RegisterAccountConfig,
+        eab_key: ExternalAccountBindingKey,
+    ) -> LocalcertResult<RegisteredState> {
+        let acme_client =
+            Client::for_directory_url(self.http_client.clone(), acme_directory_url.as_ref())
+                .await?;
+        // Sign the EAB JWS with the same key `register_config` will use to
+        // build the account, so the binding matches the account's own JWS.
+        let eab_jws = eab::build_eab_jws(
+            &eab_key,
+            &register_config.account_key,
+            &acme_client.directory().new_account,
+        )?;
+        let register_config = RegisterAccountConfig {
+            external_account_binding: Some(serde_json::to_value(eab_jws)?),
+            ..register_config
+        };
+        let account = acme_client.register_account_config(register_config).await?;
+        self.build_with_account(account)
+    }
+
     pub async fn find_account(
         self,
         acme_directory_url: impl AsRef<str>,
@@ -71,13 +124,27 @@ impl ConfigBuilder {
         self.build_with_account(account)
     }
 
+    pub async fn from_credentials(
+        self,
+        credentials: AccountCredentials,
+    ) -> LocalcertResult<RegisteredState> {
+        let account_key = acme::crypto::account_key_from_jwk(&credentials.account_key_jwk)?;
+        let acme_client =
+            Client::for_directory_url(self.http_client.clone(), &credentials.directory_url)
+                .await?;
+        let account = acme_client.account_from_parts(credentials.account_url, account_key);
+        self.build_with_account(account)
+    }
+
     pub fn build_with_account(self, acme_account: Account) -> LocalcertResult<RegisteredState> {
         let base_url = self.server_url.as_deref().unwrap_or(DEFAULT_SERVER_URL);
-        let localcert_client = LocalcertClient::new(self.http_client, base_url)?;
+        let localcert_client =
+            LocalcertClient::new(self.http_client, base_url, self.retry_policy)?;
         Ok(RegisteredState::new(
             localcert_client,
             acme_account,
             self.acme_polling_interval,
+            self.acme_polling_timeout,
         ))
     }
 }
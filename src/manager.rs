@@ -0,0 +1,150 @@
+use std::{
+    collections::{HashMap, HashSet},
+    sync::{Arc, Mutex, RwLock, Weak},
+    time::{Duration, SystemTime},
+};
+
+use async_timer::Oneshot;
+use rustls::{
+    server::{ClientHello, ResolvesServerCert},
+    sign::{self, CertifiedKey},
+    Certificate, PrivateKey, ServerName,
+};
+
+use crate::{error::LocalcertResult, states::RegisteredState};
+
+pub static DEFAULT_RENEWAL_WINDOW: Duration = Duration::from_secs(30 * 24 * 60 * 60);
+static ISSUANCE_RETRY_DELAY: Duration = Duration::from_secs(60);
+
+type StateFactory = dyn Fn(&str) -> LocalcertResult<RegisteredState> + Send + Sync;
+
+pub struct CertManager {
+    certs: RwLock<HashMap<ServerName, Arc<CertifiedKey>>>,
+    managed_domains: Mutex<HashSet<String>>,
+    state_factory: Arc<StateFactory>,
+    renewal_window: Duration,
+    weak_self: RwLock<Weak<Self>>,
+}
+
+impl CertManager {
+    pub fn new<F>(domains: Vec<String>, renewal_window: Duration, state_factory: F) -> Arc<Self>
+    where
+        F: Fn(&str) -> LocalcertResult<RegisteredState> + Send + Sync + 'static,
+    {
+        let manager = Arc::new(Self {
+            certs: RwLock::new(HashMap::new()),
+            managed_domains: Mutex::new(HashSet::new()),
+            state_factory: Arc::new(state_factory),
+            renewal_window,
+            weak_self: RwLock::new(Weak::new()),
+        });
+        *manager.weak_self.write().unwrap() = Arc::downgrade(&manager);
+
+        for domain in domains {
+            manager.managed_domains.lock().unwrap().insert(domain.clone());
+            manager.clone().spawn_renewal_loop(domain);
+        }
+
+        manager
+    }
+
+    fn spawn_renewal_loop(self: Arc<Self>, domain: String) {
+        async_std::task::spawn(async move {
+            loop {
+                let sleep_for = match self.issue_and_store(&domain).await {
+                    Ok(not_after) => not_after
+                        .checked_sub(self.renewal_window)
+                        .and_then(|renew_at| renew_at.duration_since(SystemTime::now()).ok())
+                        .unwrap_or(ISSUANCE_RETRY_DELAY),
+                    Err(_) => ISSUANCE_RETRY_DELAY,
+                };
+                <async_timer::oneshot::Timer as Oneshot>::new(sleep_for).await;
+            }
+        });
+    }
+
+    async fn issue_and_store(&self, domain: &str) -> LocalcertResult<SystemTime> {
+        let (certified_key, not_after) = issue_certificate(&self.state_factory, domain).await?;
+        if let Ok(server_name) = ServerName::try_from(domain) {
+            self.certs
+                .write()
+                .unwrap()
+                .insert(server_name, Arc::new(certified_key));
+        }
+        Ok(not_after)
+    }
+
+    fn trigger_on_demand_issuance(self: &Arc<Self>, domain: String) {
+        if !self.managed_domains.lock().unwrap().insert(domain.clone()) {
+            return;
+        }
+        // First time this domain is seen: issue now and keep it renewed for
+        // as long as the process is alive, the same as the domains the
+        // manager was constructed with.
+        self.clone().spawn_renewal_loop(domain);
+    }
+}
+
+impl ResolvesServerCert for CertManager {
+    fn resolve(&self, client_hello: ClientHello) -> Option<Arc<CertifiedKey>> {
+        let domain = client_hello.server_name()?;
+        let server_name = ServerName::try_from(domain).ok()?;
+
+        if let Some(certified_key) = self.certs.read().unwrap().get(&server_name) {
+            return Some(certified_key.clone());
+        }
+
+        if let Some(manager) = self.weak_self.read().unwrap().upgrade() {
+            manager.trigger_on_demand_issuance(domain.to_string());
+        }
+
+        None
+    }
+}
+
+async fn issue_certificate(
+    state_factory: &StateFactory,
+    domain: &str,
+) -> LocalcertResult<(CertifiedKey, SystemTime)> {
+    let registered = state_factory(domain)?;
+    let ordered = registered.new_order().await?;
+    let authorized = ordered.authorize().await?;
+    let (generated_key, mut finalized) = authorized.finalize_with_generated_key().await?;
+    let chain_pem = finalized.get_certificate().await?;
+
+    parse_certified_key(&chain_pem, generated_key.private_key_pem())
+}
+
+fn parse_certified_key(chain_pem: &str, key_pem: &str) -> LocalcertResult<(CertifiedKey, SystemTime)> {
+    let cert_chain: Vec<Certificate> = rustls_pemfile::certs(&mut chain_pem.as_bytes())
+        .unwrap_or_default()
+        .into_iter()
+        .map(Certificate)
+        .collect();
+    let leaf = cert_chain.first().ok_or_else(|| {
+        crate::error::LocalcertError::StateError("empty certificate chain".to_string())
+    })?;
+    let not_after = certificate_not_after(&leaf.0)?;
+
+    let private_key = rustls_pemfile::pkcs8_private_keys(&mut key_pem.as_bytes())
+        .ok()
+        .and_then(|mut keys| keys.pop())
+        .map(PrivateKey)
+        .ok_or_else(|| {
+            crate::error::LocalcertError::StateError("invalid generated private key".to_string())
+        })?;
+    let signing_key = sign::any_supported_type(&private_key)
+        .map_err(|_| crate::error::LocalcertError::StateError("unsupported key type".to_string()))?;
+
+    Ok((CertifiedKey::new(cert_chain, signing_key), not_after))
+}
+
+fn certificate_not_after(der: &[u8]) -> LocalcertResult<SystemTime> {
+    let (_, cert) = x509_parser::parse_x509_certificate(der).map_err(|err| {
+        crate::error::LocalcertError::StateError(format!(
+            "failed to parse issued certificate: {}",
+            err
+        ))
+    })?;
+    Ok(cert.validity().not_after.to_datetime().into())
+}
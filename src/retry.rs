@@ -0,0 +1,91 @@
+use std::{future::Future, time::Duration};
+
+use acme::{wire::problem::AcmeProblemType, AcmeError};
+use async_timer::Oneshot;
+
+use crate::error::{LocalcertError, LocalcertResult};
+
+pub static DEFAULT_MAX_ATTEMPTS: u32 = 5;
+pub static DEFAULT_BASE_DELAY: Duration = Duration::from_millis(500);
+pub static DEFAULT_MAX_DELAY: Duration = Duration::from_secs(30);
+
+#[derive(Clone, Copy, Debug)]
+pub struct RetryPolicy {
+    max_attempts: u32,
+    base_delay: Duration,
+    max_delay: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: DEFAULT_MAX_ATTEMPTS,
+            base_delay: DEFAULT_BASE_DELAY,
+            max_delay: DEFAULT_MAX_DELAY,
+        }
+    }
+}
+
+impl RetryPolicy {
+    pub fn new(max_attempts: u32, base_delay: Duration, max_delay: Duration) -> Self {
+        Self {
+            max_attempts,
+            base_delay,
+            max_delay,
+        }
+    }
+
+    pub fn max_attempts(&self) -> u32 {
+        self.max_attempts
+    }
+
+    fn backoff_for_attempt(&self, attempt: u32, retry_after: Option<Duration>) -> Duration {
+        if let Some(hint) = retry_after {
+            return hint.min(self.max_delay);
+        }
+        let exp = self.base_delay.saturating_mul(1u32 << attempt.min(16));
+        exp.min(self.max_delay)
+    }
+
+    /// Runs `operation`, retrying recoverable errors with exponential backoff
+    /// until it succeeds, a fatal error is hit, or `max_attempts` is reached.
+    pub(crate) async fn run<T, F, Fut>(&self, mut operation: F) -> LocalcertResult<T>
+    where
+        F: FnMut() -> Fut,
+        Fut: Future<Output = LocalcertResult<T>>,
+    {
+        let mut attempt = 0;
+        loop {
+            match operation().await {
+                Ok(value) => return Ok(value),
+                Err(err) => {
+                    attempt += 1;
+                    if !is_recoverable(&err) || attempt >= self.max_attempts {
+                        return Err(err);
+                    }
+                    let delay = self.backoff_for_attempt(attempt - 1, retry_after_hint(&err));
+                    <async_timer::oneshot::Timer as Oneshot>::new(delay).await;
+                }
+            }
+        }
+    }
+}
+
+fn is_recoverable(err: &LocalcertError) -> bool {
+    match err {
+        LocalcertError::AcmeError(AcmeError::AcmeProblem(problem)) => {
+            problem.has_type(AcmeProblemType::BadNonce)
+                || problem.has_type(AcmeProblemType::RateLimited)
+                || problem.has_type(AcmeProblemType::ServerInternal)
+        }
+        LocalcertError::HttpError(err) => err.status().is_server_error(),
+        _ => false,
+    }
+}
+
+fn retry_after_hint(err: &LocalcertError) -> Option<Duration> {
+    match err {
+        LocalcertError::AcmeError(AcmeError::AcmeProblem(problem)) => problem.retry_after(),
+        _ => None,
+    }
+}
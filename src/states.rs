@@ -1,4 +1,4 @@
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
 use acme::{
     api::{
@@ -10,18 +10,21 @@ use acme::{
     wire::{
         authorization::AuthorizationStatus, challenge::CHALLENGE_TYPE_DNS_01, order::OrderStatus,
     },
+    AcmeError,
 };
 use async_timer::Oneshot;
 
 use crate::{
     error::{LocalcertError, LocalcertResult},
     wire::client::LocalcertClient,
+    AccountCredentials,
 };
 
 pub struct RegisteredState {
     client: LocalcertClient,
     account: Account,
     acme_polling_interval: Duration,
+    acme_polling_timeout: Duration,
 }
 
 impl RegisteredState {
@@ -29,11 +32,13 @@ impl RegisteredState {
         client: LocalcertClient,
         account: Account,
         acme_polling_interval: Duration,
+        acme_polling_timeout: Duration,
     ) -> Self {
         Self {
             client,
             account,
             acme_polling_interval,
+            acme_polling_timeout,
         }
     }
 
@@ -41,9 +46,29 @@ impl RegisteredState {
         &self.account
     }
 
+    pub fn credentials(&self) -> LocalcertResult<AccountCredentials> {
+        Ok(AccountCredentials {
+            account_url: self.account.url().to_string(),
+            account_key_jwk: self
+                .account
+                .key()
+                .private_jwk()
+                .map_err(AcmeError::CryptoError)?,
+            directory_url: self.account.client().directory_url().to_string(),
+        })
+    }
+
     pub async fn new_order(self) -> LocalcertResult<OrderedState> {
         let domain_result = self.client.get_domain(&self.account).await?;
-        let order = self.account.new_dns_order(domain_result.domain).await?;
+        self.new_order_with_identifiers(vec![domain_result.localcert_domain])
+            .await
+    }
+
+    pub async fn new_order_with_identifiers(
+        self,
+        identifiers: Vec<String>,
+    ) -> LocalcertResult<OrderedState> {
+        let order = self.account.new_dns_order_many(identifiers).await?;
         Ok(self.with_order(order))
     }
 
@@ -53,6 +78,7 @@ impl RegisteredState {
             account: self.account,
             order: acme_order,
             acme_polling_interval: self.acme_polling_interval,
+            acme_polling_timeout: self.acme_polling_timeout,
         })
     }
 
@@ -63,6 +89,7 @@ impl RegisteredState {
             account: self.account,
             order,
             acme_polling_interval: self.acme_polling_interval,
+            acme_polling_timeout: self.acme_polling_timeout,
         };
         Ok(match state.order.status_result()? {
             OrderStatus::Pending => ResumeOrderState::Ordered(OrderedState(state)),
@@ -86,6 +113,7 @@ struct State {
     account: Account,
     order: Order,
     acme_polling_interval: Duration,
+    acme_polling_timeout: Duration,
 }
 
 impl State {
@@ -93,11 +121,25 @@ impl State {
         &mut self,
         status: OrderStatus,
     ) -> LocalcertResult<OrderStatus> {
-        if self.order.status() == status {
-            // TODO: timeout
-            self.order
-                .status_changed(|| {
-                    <async_timer::oneshot::Timer as Oneshot>::new(self.acme_polling_interval)
+        let deadline = Instant::now() + self.acme_polling_timeout;
+        let retry_policy = self.client.retry_policy();
+        while self.order.status() == status {
+            if Instant::now() >= deadline {
+                return Err(LocalcertError::StateError(format!(
+                    "order stuck in {:?} status after {:?} of polling",
+                    status, self.acme_polling_timeout
+                )));
+            }
+            let order = &mut self.order;
+            let acme_polling_interval = self.acme_polling_interval;
+            retry_policy
+                .run(|| async {
+                    order
+                        .status_changed(|| {
+                            <async_timer::oneshot::Timer as Oneshot>::new(acme_polling_interval)
+                        })
+                        .await
+                        .map_err(LocalcertError::from)
                 })
                 .await?;
         }
@@ -113,9 +155,14 @@ impl OrderedState {
     }
 
     pub async fn authorize(mut self) -> LocalcertResult<AuthorizedState> {
+        let retry_policy = self.0.client.retry_policy();
         if let OrderState::Pending(ref pending) = self.0.order.state_result()? {
-            let mut authorization = pending.get_only_authorization().await?;
-            authorize(&self.0.client, &self.0.account, &mut authorization).await?;
+            let mut authorizations = retry_policy
+                .run(|| async { pending.get_authorizations().await.map_err(LocalcertError::from) })
+                .await?;
+            for authorization in authorizations.iter_mut() {
+                authorize(&self.0.client, &self.0.account, authorization).await?;
+            }
             self.0
                 .order_status_changed_from(OrderStatus::Pending)
                 .await?;
@@ -130,9 +177,17 @@ impl AuthorizedState {
     pub async fn finalize_with_generated_key(
         mut self,
     ) -> LocalcertResult<(GeneratedKey, FinalizedState)> {
+        let retry_policy = self.0.client.retry_policy();
         match self.0.order.state_result()? {
             OrderState::Ready(mut ready) => {
-                let generated_key = ready.finalize_with_generated_key().await?;
+                let generated_key = retry_policy
+                    .run(|| async {
+                        ready
+                            .finalize_with_generated_key()
+                            .await
+                            .map_err(LocalcertError::from)
+                    })
+                    .await?;
                 Ok((generated_key, FinalizedState(self.0)))
             }
             _ => Err(LocalcertError::unexpected_status(
@@ -146,9 +201,13 @@ impl AuthorizedState {
         mut self,
         csr_der: impl AsRef<[u8]>,
     ) -> LocalcertResult<FinalizedState> {
+        let retry_policy = self.0.client.retry_policy();
+        let csr_der = csr_der.as_ref();
         match self.0.order.state_result()? {
             OrderState::Ready(mut ready) => {
-                ready.finalize(csr_der).await?;
+                retry_policy
+                    .run(|| async { ready.finalize(csr_der).await.map_err(LocalcertError::from) })
+                    .await?;
             }
             OrderState::Pending(_) => {
                 return Err(LocalcertError::unexpected_status(
@@ -170,9 +229,14 @@ impl FinalizedState {
             .0
             .order_status_changed_from(OrderStatus::Processing)
             .await?;
+        let retry_policy = self.0.client.retry_policy();
         match self.0.order.state_result()? {
-            OrderState::Valid(ref valid) => Ok(valid.get_certificate_chain().await?),
-            _ => return Err(LocalcertError::unexpected_status("order", status)),
+            OrderState::Valid(ref valid) => {
+                retry_policy
+                    .run(|| async { valid.get_certificate_chain().await.map_err(LocalcertError::from) })
+                    .await
+            }
+            _ => Err(LocalcertError::unexpected_status("order", status)),
         }
     }
 }
@@ -187,7 +251,9 @@ async fn authorize(
         AuthorizationStatus::Valid => {
             return Ok(());
         }
-        _ => unreachable!(),
+        status => {
+            return Err(LocalcertError::unexpected_status("authorization", status));
+        }
     }
 
     let mut challenge = authorization
@@ -205,7 +271,10 @@ async fn authorize(
     }
 
     if let ChallengeState::Pending(mut pending) = challenge.state_result()? {
-        pending.respond().await?;
+        client
+            .retry_policy()
+            .run(|| async { pending.respond().await.map_err(LocalcertError::from) })
+            .await?;
     }
     Ok(())
 }
@@ -10,7 +10,6 @@ use acme::{
     wire::{
         account::NewAccountResource,
         client::{Auth, NO_PAYLOAD},
-        problem::AcmeProblemType,
     },
     AcmeError,
 };
@@ -19,15 +18,23 @@ use super::{
     domain::{DomainRequest, DomainResult},
     provision::{ProvisionRequest, ProvisionResult},
 };
-use crate::error::{LocalcertError, LocalcertResult};
+use crate::{
+    error::{LocalcertError, LocalcertResult},
+    retry::RetryPolicy,
+};
 
 pub struct LocalcertClient {
     http: Arc<dyn HttpClient>,
     base_url: Url,
+    retry_policy: RetryPolicy,
 }
 
 impl LocalcertClient {
-    pub fn new<U>(http: impl Into<Arc<dyn HttpClient>>, base_url: U) -> LocalcertResult<Self>
+    pub fn new<U>(
+        http: impl Into<Arc<dyn HttpClient>>,
+        base_url: U,
+        retry_policy: RetryPolicy,
+    ) -> LocalcertResult<Self>
     where
         U: TryInto<Url>,
         U::Error: Display,
@@ -45,15 +52,18 @@ impl LocalcertClient {
         Ok(Self {
             http: http.into(),
             base_url: url,
+            retry_policy,
         })
     }
 
+    pub(crate) fn retry_policy(&self) -> RetryPolicy {
+        self.retry_policy
+    }
+
     pub async fn get_domain(&self, account: &Account) -> LocalcertResult<DomainResult> {
-        let mut res = self.get_domain_once(account).await;
-        if is_bad_nonce_error(&res) {
-            res = self.get_domain_once(account).await;
-        }
-        res
+        self.retry_policy
+            .run(|| self.get_domain_once(account))
+            .await
     }
 
     async fn get_domain_once(&self, account: &Account) -> LocalcertResult<DomainResult> {
@@ -81,11 +91,9 @@ impl LocalcertClient {
         account: &Account,
         authorization_url: &str,
     ) -> LocalcertResult<ProvisionResult> {
-        let mut res = self.provision_domain_once(account, authorization_url).await;
-        if is_bad_nonce_error(&res) {
-            res = self.provision_domain_once(account, authorization_url).await;
-        }
-        res
+        self.retry_policy
+            .run(|| self.provision_domain_once(account, authorization_url))
+            .await
     }
 
     async fn provision_domain_once(
@@ -134,11 +142,3 @@ impl LocalcertClient {
         Ok(resp.body_json().await?)
     }
 }
-
-pub(crate) fn is_bad_nonce_error<T>(res: &LocalcertResult<T>) -> bool {
-    if let Err(LocalcertError::AcmeError(AcmeError::AcmeProblem(ref problem))) = res {
-        problem.has_type(AcmeProblemType::BadNonce)
-    } else {
-        false
-    }
-}